@@ -0,0 +1,528 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use clap::Args as ClapArgs;
+use filetime::FileTime;
+use rayon::prelude::*;
+
+use crate::hashing::{hash_file, HashAlgo};
+use crate::matchlist::MatchList;
+use crate::meta::{
+    construct_hash_sidecar_content, construct_meta_content, hash_sidecar_extension, parse_hash_sidecar_content,
+    parse_meta_content, FileAttrs,
+};
+use crate::NON_UNICODE_PATH;
+
+/// Generates a meta directory structure to remember files on unplugged drives.
+/// Standard output lists properly processed files separated by newlines.
+#[derive(ClapArgs, Debug)]
+pub struct GenerateArgs {
+    source: PathBuf,
+    destination: PathBuf,
+
+    /// Max size in bytes to copy file unchanged.
+    #[arg(short, long, value_name = "SIZE_IN_BYTES", default_value = "0")]
+    max_size: u64,
+
+    /// Extension for meta files.
+    #[arg(short, long, value_name = "EXTENSION", default_value = ".drivetan.txt")]
+    extension: String,
+
+    /// Magic at the start of a meta file.
+    #[arg(long, value_name = "MAGIC", default_value = "DRIVETAN")]
+    magic: String,
+
+    /// Algorithm used to hash file contents for the `verify` mode.
+    #[arg(long, value_enum, default_value_t = HashAlgo::Blake3)]
+    hash_algo: HashAlgo,
+
+    /// Skip files/directories matching the ordered include/exclude rules in
+    /// the provided file, one rule per line (e.g. "\.git"). A `!` prefix
+    /// makes a rule an include, overriding an earlier exclude; a leading
+    /// `/` anchors the pattern to the full relative path instead of just
+    /// the basename; a trailing `/` restricts it to directories. The last
+    /// rule to match an entry wins. See [`crate::matchlist`].
+    #[arg(long, value_name = "PATH")]
+    skip_file: Option<PathBuf>,
+
+    /// Number of worker threads to copy/hash files with. Defaults to the
+    /// number of available CPUs.
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Diff against an existing meta tree instead of requiring an empty
+    /// destination: add entries for new source files, rewrite ones whose
+    /// recorded size or mtime no longer matches the source, and leave
+    /// everything else untouched.
+    #[arg(short, long)]
+    update: bool,
+
+    /// With `--update`, also delete meta entries whose source file no
+    /// longer exists. Has no effect without `--update`.
+    #[arg(long)]
+    prune: bool,
+}
+
+pub fn run(args: &GenerateArgs) -> anyhow::Result<()> {
+    check_args(args)?;
+
+    let matchlist = match &args.skip_file {
+        Some(skip_file) => MatchList::parse(&match std::fs::read_to_string(skip_file) {
+            Ok(it) => it,
+            Err(err) => anyhow::bail!(
+                "could not read file {}: {}",
+                skip_file.to_str().unwrap_or(NON_UNICODE_PATH),
+                err
+            ),
+        })?,
+        None => MatchList::empty(),
+    };
+
+    let mut error_entries = 0u64;
+    let mut skipped_entries = 0u64;
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+
+    let mut walker = walkdir::WalkDir::new(&args.source).into_iter();
+    while let Some(entry) = walker.next() {
+        match entry {
+            Err(err) => {
+                eprintln!("could not read directory entry: {}", err);
+                error_entries += 1;
+            }
+            Ok(entry) => {
+                let is_dir = entry.file_type().is_dir();
+                let diff = pathdiff::diff_paths(entry.path(), &args.source).unwrap_or_default();
+
+                if matchlist.is_excluded(&diff, is_dir) {
+                    skipped_entries += 1;
+                    // An excluded directory is pruned whole, rather than
+                    // descending just to skip every child individually.
+                    if is_dir {
+                        walker.skip_current_dir();
+                    }
+                    continue;
+                }
+
+                match entry.metadata() {
+                    Ok(meta) if meta.is_dir() => dirs.push(entry),
+                    Ok(_) => files.push(entry),
+                    Err(err) => {
+                        eprintln!(
+                            "could not stat directory entry {}: {}",
+                            entry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+                            err
+                        );
+                        error_entries += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // Directories are walked (and thus created) parent-before-child, so
+    // doing this pass sequentially and up front guarantees every directory
+    // a file might land in already exists before the parallel file pass
+    // below starts writing into it.
+    let mut success_entries = 0u64;
+    for entry in &dirs {
+        match create_directory(args, entry) {
+            Err(err) => {
+                eprintln!(
+                    "handling directory entry {} failed: {}",
+                    entry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+                    err
+                );
+                error_entries += 1;
+            }
+            Ok(is_new) => {
+                if is_new {
+                    println!("{}", entry.path().to_str().unwrap_or(NON_UNICODE_PATH));
+                    success_entries += 1;
+                }
+            }
+        }
+    }
+
+    let mut pool_builder = rayon::ThreadPoolBuilder::new();
+    if let Some(jobs) = args.jobs {
+        pool_builder = pool_builder.num_threads(jobs);
+    }
+    let pool = pool_builder.build()?;
+
+    let success_entries = AtomicU64::new(success_entries);
+    let error_entries = AtomicU64::new(error_entries);
+    let unchanged_entries = AtomicU64::new(0);
+
+    // `println!`/`eprintln!` each take the stdout/stderr lock for the whole
+    // line they write, so the success list below stays one-entry-per-line
+    // even when many worker threads are writing to it concurrently.
+    pool.install(|| {
+        files.par_iter().for_each(|entry| match handle_file(args, entry) {
+            Err(err) => {
+                eprintln!(
+                    "handling directory entry {} failed: {}",
+                    entry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+                    err
+                );
+                error_entries.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(EntryOutcome::Unchanged) => {
+                unchanged_entries.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(_) => {
+                println!("{}", entry.path().to_str().unwrap_or(NON_UNICODE_PATH));
+                success_entries.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+    });
+
+    let success_entries = success_entries.into_inner();
+    let error_entries = error_entries.into_inner();
+    let unchanged_entries = unchanged_entries.into_inner();
+
+    let pruned_entries = if args.update && args.prune {
+        prune_orphans(args)?
+    } else {
+        0
+    };
+
+    if success_entries == 0 && (error_entries > 0 || (dirs.is_empty() && files.is_empty())) {
+        anyhow::bail!(
+            "no entries successfuly processed; error count: {}",
+            error_entries
+        );
+    }
+
+    eprintln!(
+        "success count: {}, error count: {}, skipped count: {}, unchanged count: {}, pruned count: {}",
+        success_entries, error_entries, skipped_entries, unchanged_entries, pruned_entries
+    );
+    Ok(())
+}
+
+/// Creates the destination directory for a source directory entry, returning
+/// whether it needed to be created (as opposed to already existing from a
+/// previous `generate` run in `--update` mode).
+fn create_directory(args: &GenerateArgs, direntry: &walkdir::DirEntry) -> anyhow::Result<bool> {
+    let diff = pathdiff::diff_paths(direntry.path(), &args.source).ok_or_else(|| {
+        anyhow::anyhow!(
+            "could not diff paths: {}, {}",
+            direntry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+            args.source.to_str().unwrap_or(NON_UNICODE_PATH)
+        )
+    })?;
+
+    let dest = args.destination.join(diff);
+    let is_new = !dest.exists();
+    std::fs::create_dir_all(dest)?;
+    Ok(is_new)
+}
+
+/// What happened to a file entry while processing it.
+enum EntryOutcome {
+    Added,
+    Updated,
+    Unchanged,
+}
+
+/// The size/mtime previously recorded for a file entry, read back from the
+/// `mtime:` line in its meta tree counterpart rather than that counterpart's
+/// own filesystem mtime, which `--update` used to compare against: writing
+/// that file's times with `filetime::set_file_times` can round-trip through
+/// less precision than a plain stat does on some filesystems, so the fs
+/// mtime could drift from the source's and force a spurious rewrite.
+struct RecordedEntry {
+    size: u64,
+    mtime: FileTime,
+}
+
+/// Looks up what's currently recorded in the meta tree for a source file, if
+/// anything, without touching the source file itself. Used by `--update` to
+/// decide whether an entry can be left alone.
+fn recorded_entry(
+    placeholder_path: &Path,
+    verbatim_path: &Path,
+    sidecar_path: &Path,
+    args: &GenerateArgs,
+) -> anyhow::Result<Option<RecordedEntry>> {
+    if placeholder_path.exists() {
+        let parsed = parse_meta_content(&std::fs::read(placeholder_path)?, &args.magic)?;
+        return Ok(Some(RecordedEntry {
+            size: parsed.size,
+            mtime: parsed.attrs.mtime,
+        }));
+    }
+
+    if verbatim_path.exists() {
+        let size = std::fs::metadata(verbatim_path)?.len();
+        let parsed = parse_hash_sidecar_content(&std::fs::read(sidecar_path)?)?;
+        return Ok(Some(RecordedEntry {
+            size,
+            mtime: parsed.attrs.mtime,
+        }));
+    }
+
+    Ok(None)
+}
+
+fn handle_file(args: &GenerateArgs, direntry: &walkdir::DirEntry) -> anyhow::Result<EntryOutcome> {
+    let diff = pathdiff::diff_paths(direntry.path(), &args.source);
+
+    match diff {
+        None => anyhow::bail!(
+            "could not diff paths: {}, {}",
+            direntry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+            &args.source.to_str().unwrap_or(NON_UNICODE_PATH)
+        ),
+        Some(diff) => {
+            let meta = direntry.metadata()?;
+
+            let dest = args.destination.join(&diff);
+
+            let mut placeholder_path = dest.clone();
+            let mut placeholder_name = placeholder_path
+                .file_name()
+                .expect("not dir, should have a file name")
+                .to_os_string();
+            placeholder_name.push(&args.extension);
+            placeholder_path.set_file_name(placeholder_name);
+
+            let mut sidecar_name = dest
+                .file_name()
+                .expect("not dir, should have a file name")
+                .to_os_string();
+            sidecar_name.push(hash_sidecar_extension(&args.extension));
+            let sidecar_path = dest.with_file_name(sidecar_name);
+
+            let recorded = if args.update {
+                recorded_entry(&placeholder_path, &dest, &sidecar_path, args)?
+            } else {
+                None
+            };
+
+            let current_mtime = FileTime::from_last_modification_time(&meta);
+
+            let outcome = match &recorded {
+                Some(rec) if rec.size == meta.len() && rec.mtime == current_mtime => {
+                    return Ok(EntryOutcome::Unchanged);
+                }
+                Some(_) => EntryOutcome::Updated,
+                None => EntryOutcome::Added,
+            };
+
+            if recorded.is_some() {
+                // The entry may be switching representations (e.g. crossing
+                // `max_size` in either direction), so clear out whichever of
+                // the two forms was previously recorded before writing the
+                // fresh one.
+                if placeholder_path.exists() {
+                    std::fs::remove_file(&placeholder_path)?;
+                }
+                if dest.exists() {
+                    std::fs::remove_file(&dest)?;
+                }
+                if sidecar_path.exists() {
+                    std::fs::remove_file(&sidecar_path)?;
+                }
+            }
+
+            let hash = hash_file(direntry.path(), args.hash_algo)?;
+            let attrs = FileAttrs {
+                mtime: current_mtime,
+                atime: FileTime::from_last_access_time(&meta),
+                mode: meta.permissions().mode(),
+            };
+
+            let written = if meta.len() > args.max_size {
+                write_atomically(
+                    &placeholder_path,
+                    &construct_meta_content(&args.magic, meta.len(), Some(&hash), &attrs),
+                )?;
+                &placeholder_path
+            } else {
+                sparse_copy(direntry.path(), &dest)?;
+                // `sparse_copy` creates `dest` fresh, which drops the
+                // source's permission bits; reapply them, as `fs::copy` did.
+                std::fs::set_permissions(&dest, meta.permissions())?;
+                write_atomically(&sidecar_path, &construct_hash_sidecar_content(&hash, &attrs))?;
+                &dest
+            };
+
+            // NOTE: even if this fails, file is already created, which is fine.
+            //       the only issue arises from the fact it's not stdouted with the rest and
+            //       error is displayed.
+            filetime::set_file_times(written, meta.accessed()?.into(), meta.modified()?.into())?;
+
+            Ok(outcome)
+        }
+    }
+}
+
+/// Chunk size used when streaming a file through [`sparse_copy`].
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Granularity at which [`sparse_copy`] looks for zero runs to punch holes
+/// for, matching the block size most filesystems allocate sparse files in.
+const HOLE_GRANULARITY: usize = 4096;
+
+/// Writes `content` to `path` by first writing to a temporary sibling file
+/// and renaming it into place, so a run that's interrupted mid-write never
+/// leaves a half-written meta file at `path` (the same atomic file-swap
+/// approach `sd` uses for in-place edits).
+fn write_atomically(path: &Path, content: &[u8]) -> anyhow::Result<()> {
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(path.file_name().expect("path should have a file name"));
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Copies `src` to `dest`, detecting runs of zero bytes and seeking over
+/// them instead of writing, so holes in a sparse source file stay
+/// unallocated in the destination rather than being inflated (as pxar's
+/// `sparse_copy` does). Zero runs are detected at [`HOLE_GRANULARITY`]
+/// resolution within each chunk read, not just whole-chunk, so holes
+/// smaller than or unaligned to [`CHUNK_SIZE`] are still preserved.
+fn sparse_copy(src: &Path, dest: &Path) -> anyhow::Result<()> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let mut src_file = std::fs::File::open(src)?;
+    let mut dest_file = std::fs::File::create(dest)?;
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut len = 0u64;
+
+    loop {
+        let read = src_file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+
+        let mut offset = 0;
+        while offset < read {
+            let end = (offset + HOLE_GRANULARITY).min(read);
+            let block = &buf[offset..end];
+
+            if block.iter().all(|&b| b == 0) {
+                dest_file.seek(SeekFrom::Current(block.len() as i64))?;
+            } else {
+                dest_file.write_all(block)?;
+            }
+
+            offset = end;
+        }
+
+        len += read as u64;
+    }
+
+    dest_file.set_len(len)?;
+
+    Ok(())
+}
+
+/// With `--update --prune`, walks the existing meta tree and deletes entries
+/// whose source file no longer exists. Mirrors [`crate::verify`]'s
+/// extra-entry detection, but removes rather than just reports them.
+fn prune_orphans(args: &GenerateArgs) -> anyhow::Result<u64> {
+    let mut pruned = 0u64;
+
+    for entry in walkdir::WalkDir::new(&args.destination) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                eprintln!("could not read destination directory entry: {}", err);
+                continue;
+            }
+        };
+
+        if entry.file_type().is_dir() {
+            continue;
+        }
+
+        let file_name = match entry.file_name().to_str() {
+            Some(it) => it,
+            None => {
+                eprintln!("skipping non-unicode file name in meta tree during prune");
+                continue;
+            }
+        };
+
+        if file_name.ends_with(&hash_sidecar_extension(&args.extension)) {
+            // Visited alongside its verbatim copy below.
+            continue;
+        }
+
+        let diff = pathdiff::diff_paths(entry.path(), &args.destination).ok_or_else(|| {
+            anyhow::anyhow!(
+                "could not diff paths: {}, {}",
+                entry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+                args.destination.to_str().unwrap_or(NON_UNICODE_PATH)
+            )
+        })?;
+
+        let mut source_path = args.source.join(&diff);
+        let is_placeholder = match file_name.strip_suffix(&args.extension) {
+            Some(original_name) => {
+                source_path.set_file_name(original_name);
+                true
+            }
+            None => false,
+        };
+
+        if source_path.exists() {
+            continue;
+        }
+
+        std::fs::remove_file(entry.path())?;
+
+        if !is_placeholder {
+            let mut sidecar_name = entry.file_name().to_os_string();
+            sidecar_name.push(hash_sidecar_extension(&args.extension));
+            let sidecar_path = entry.path().with_file_name(sidecar_name);
+            if sidecar_path.exists() {
+                std::fs::remove_file(&sidecar_path)?;
+            }
+        }
+
+        println!("pruned: {}", entry.path().to_str().unwrap_or(NON_UNICODE_PATH));
+        pruned += 1;
+    }
+
+    Ok(pruned)
+}
+
+fn check_args(args: &GenerateArgs) -> anyhow::Result<()> {
+    if !args.source.exists() {
+        anyhow::bail!(
+            "source path {} does not exist or cannot be accessed",
+            args.source.to_str().unwrap_or(NON_UNICODE_PATH)
+        );
+    }
+
+    if !args.destination.exists() {
+        match std::fs::create_dir(&args.destination) {
+            Ok(it) => it,
+            Err(err) => anyhow::bail!("creating destination directory failed: {}", err),
+        };
+    } else if !args.update
+        && match std::fs::read_dir(&args.destination) {
+            Ok(it) => it,
+            Err(err) => anyhow::bail!("reading destination directory failed: {}", err),
+        }
+        .next()
+        .is_some()
+    {
+        anyhow::bail!(
+            "destination path {} is not empty (use --update to incrementally update an existing meta tree)",
+            args.destination.to_str().unwrap_or(NON_UNICODE_PATH)
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Streaming content hashing used to embed and later check digests in meta files.
+
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+/// Chunk size used when streaming a file through the hasher, so large files
+/// never need to be read wholly into memory.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgo {
+    Blake3,
+    Sha256,
+}
+
+impl fmt::Display for HashAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HashAlgo::Blake3 => "blake3",
+            HashAlgo::Sha256 => "sha256",
+        })
+    }
+}
+
+impl FromStr for HashAlgo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(HashAlgo::Blake3),
+            "sha256" => Ok(HashAlgo::Sha256),
+            other => anyhow::bail!("unknown hash algorithm {:?}", other),
+        }
+    }
+}
+
+/// A digest together with the algorithm that produced it, in the
+/// `algo:hexdigest` form stored in meta files.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Digest {
+    pub algo: HashAlgo,
+    pub hex: String,
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algo, self.hex)
+    }
+}
+
+impl FromStr for Digest {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algo, hex) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("malformed hash {:?}, expected algo:hexdigest", s))?;
+
+        Ok(Digest {
+            algo: algo.parse()?,
+            hex: hex.to_owned(),
+        })
+    }
+}
+
+/// Streams `path` through `algo` in fixed-size chunks and returns its digest.
+pub fn hash_file(path: &Path, algo: HashAlgo) -> anyhow::Result<Digest> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+
+    let hex = match algo {
+        HashAlgo::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            loop {
+                let read = file.read(&mut buf)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buf[..read]);
+            }
+            hasher.finalize().to_hex().to_string()
+        }
+        HashAlgo::Sha256 => {
+            use sha2::Digest as _;
+            let mut hasher = sha2::Sha256::new();
+            loop {
+                let read = file.read(&mut buf)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buf[..read]);
+            }
+            to_hex(&hasher.finalize())
+        }
+    };
+
+    Ok(Digest { algo, hex })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
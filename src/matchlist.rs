@@ -0,0 +1,111 @@
+//! Ordered include/exclude pattern engine used to prune the `generate` walk.
+//!
+//! Modeled on pxar's `MatchEntry`/`MatchList`: each line is a rule carrying a
+//! [`MatchType`] of include or exclude, rules are evaluated top-to-bottom,
+//! and the last rule that matches an entry decides its verdict.
+
+use std::path::Path;
+
+use regex::bytes::Regex;
+
+/// Whether a rule marks matching entries as excluded or forces them back in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MatchType {
+    Include,
+    Exclude,
+}
+
+/// What part of the entry a rule's pattern is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    /// Matched against the full path, relative to the walk root.
+    FullPath,
+    /// Matched against just the entry's final path component.
+    Basename,
+}
+
+struct MatchEntry {
+    ty: MatchType,
+    anchor: Anchor,
+    dir_only: bool,
+    regex: Regex,
+}
+
+impl MatchEntry {
+    /// Parses a single rule line. A leading `!` makes the rule an include,
+    /// a leading `/` anchors the pattern to the full relative path instead
+    /// of just the basename, and a trailing `/` restricts it to directories.
+    fn parse(line: &str) -> anyhow::Result<Self> {
+        let (ty, line) = match line.strip_prefix('!') {
+            Some(rest) => (MatchType::Include, rest),
+            None => (MatchType::Exclude, line),
+        };
+
+        let (anchor, line) = match line.strip_prefix('/') {
+            Some(rest) => (Anchor::FullPath, rest),
+            None => (Anchor::Basename, line),
+        };
+
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        Ok(MatchEntry {
+            ty,
+            anchor,
+            dir_only,
+            regex: Regex::new(line)?,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        match self.anchor {
+            Anchor::FullPath => self.regex.is_match(path.as_os_str().as_encoded_bytes()),
+            Anchor::Basename => match path.file_name() {
+                Some(name) => self.regex.is_match(name.as_encoded_bytes()),
+                None => false,
+            },
+        }
+    }
+}
+
+/// An ordered list of include/exclude rules (see [`MatchEntry`]).
+pub struct MatchList {
+    entries: Vec<MatchEntry>,
+}
+
+impl MatchList {
+    /// Parses one rule per non-empty line, in the syntax documented on
+    /// [`MatchEntry::parse`].
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let entries = content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(MatchEntry::parse)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(MatchList { entries })
+    }
+
+    /// An empty list, under which nothing is ever excluded.
+    pub fn empty() -> Self {
+        MatchList { entries: Vec::new() }
+    }
+
+    /// Whether `path` (relative to the walk root) should be excluded,
+    /// i.e. whichever rule matches it last, if any.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let mut excluded = false;
+        for entry in &self.entries {
+            if entry.matches(path, is_dir) {
+                excluded = entry.ty == MatchType::Exclude;
+            }
+        }
+        excluded
+    }
+}
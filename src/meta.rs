@@ -0,0 +1,208 @@
+//! Shared format for `*.drivetan.txt` meta files and their hash sidecars.
+
+use filetime::FileTime;
+
+use crate::hashing::Digest;
+
+/// A file's modification/access time and permission bits, recorded in meta
+/// files and hash sidecars. Reading these back from the meta content itself
+/// (rather than from the meta/sidecar file's own filesystem timestamps and
+/// mode) keeps a meta tree self-describing across a copy, tar, or rsync to
+/// another machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileAttrs {
+    pub mtime: FileTime,
+    pub atime: FileTime,
+    pub mode: u32,
+}
+
+/// The fields recovered from parsing a meta file's contents.
+pub struct ParsedMeta {
+    pub size: u64,
+    pub hash: Option<Digest>,
+    pub attrs: FileAttrs,
+}
+
+/// The fields recovered from parsing a hash sidecar's contents.
+pub struct ParsedHashSidecar {
+    pub hash: Digest,
+    pub attrs: FileAttrs,
+}
+
+/// Builds the contents of a meta file for a file of the given size and
+/// attributes, optionally embedding a content digest alongside the size.
+pub fn construct_meta_content(magic: &str, len: u64, hash: Option<&Digest>, attrs: &FileAttrs) -> Vec<u8> {
+    let human_size = if len < 1024 {
+        format!("{} B", len)
+    } else if len < 1024 * 1024 {
+        format!("{:.2} KiB", len as f64 / 1024.0)
+    } else if len < 1024 * 1024 * 1024 {
+        format!("{:.2} MiB", len as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.2} GiB", len as f64 / (1024.0 * 1024.0 * 1024.0))
+    };
+
+    let mut result = format!(
+        "{}
+
+size:       {}
+human_size: {}
+",
+        magic, len, human_size
+    );
+
+    if let Some(hash) = hash {
+        result.push_str(&format!("hash:       {}\n", hash));
+    }
+
+    result.push_str(&format_attrs(attrs));
+
+    result.into()
+}
+
+/// Parses the contents of a meta file, validating the leading magic line
+/// and extracting the recorded `size:` (and `hash:`, if present) along with
+/// its [`FileAttrs`].
+///
+/// Returns an error if the magic doesn't match or a field is missing or
+/// malformed, since a meta tree with unreadable entries shouldn't be
+/// silently skipped.
+pub fn parse_meta_content(content: &[u8], magic: &str) -> anyhow::Result<ParsedMeta> {
+    let content =
+        std::str::from_utf8(content).map_err(|err| anyhow::anyhow!("meta file is not valid utf-8: {}", err))?;
+    let mut lines = content.lines();
+
+    match lines.next() {
+        Some(line) if line == magic => {}
+        Some(other) => anyhow::bail!("unexpected magic {:?}, expected {:?}", other, magic),
+        None => anyhow::bail!("meta file is empty, expected magic {:?}", magic),
+    }
+
+    let mut size = None;
+    let mut hash = None;
+    let mut attrs = ParsedAttrs::default();
+
+    for line in lines {
+        if let Some(rest) = line.strip_prefix("size:") {
+            size = Some(
+                rest.trim()
+                    .parse::<u64>()
+                    .map_err(|err| anyhow::anyhow!("meta file has a malformed size: {}", err))?,
+            );
+        } else if let Some(rest) = line.strip_prefix("hash:") {
+            hash = Some(rest.trim().parse()?);
+        } else {
+            attrs.consume_line(line)?;
+        }
+    }
+
+    Ok(ParsedMeta {
+        size: size.ok_or_else(|| anyhow::anyhow!("meta file is missing a size: line"))?,
+        hash,
+        attrs: attrs.finish("meta file")?,
+    })
+}
+
+/// Suffix appended to a verbatim copy's file name for the sidecar that
+/// carries the hash of that copy (see [`crate::hashing`]).
+pub fn hash_sidecar_extension(extension: &str) -> String {
+    format!("{}.hash", extension)
+}
+
+/// Builds the sidecar contents recorded alongside a verbatim copy.
+pub fn construct_hash_sidecar_content(hash: &Digest, attrs: &FileAttrs) -> Vec<u8> {
+    let mut result = format!("hash:       {}\n", hash);
+    result.push_str(&format_attrs(attrs));
+    result.into()
+}
+
+/// Parses a hash sidecar's contents back into a [`Digest`] and [`FileAttrs`].
+pub fn parse_hash_sidecar_content(content: &[u8]) -> anyhow::Result<ParsedHashSidecar> {
+    let content = std::str::from_utf8(content)
+        .map_err(|err| anyhow::anyhow!("hash sidecar is not valid utf-8: {}", err))?;
+
+    let mut hash = None;
+    let mut attrs = ParsedAttrs::default();
+
+    for line in content.lines() {
+        if let Some(rest) = line.strip_prefix("hash:") {
+            hash = Some(rest.trim().parse()?);
+        } else {
+            attrs.consume_line(line)?;
+        }
+    }
+
+    Ok(ParsedHashSidecar {
+        hash: hash.ok_or_else(|| anyhow::anyhow!("hash sidecar is missing a hash: line"))?,
+        attrs: attrs.finish("hash sidecar")?,
+    })
+}
+
+/// Formats the `mtime:`/`atime:`/`mode:` lines shared by meta files and hash
+/// sidecars. Times are stored as `seconds.nanoseconds` since the Unix epoch
+/// so they survive being copied to another machine, and mode as octal.
+fn format_attrs(attrs: &FileAttrs) -> String {
+    format!(
+        "mtime:      {}.{:09}\natime:      {}.{:09}\nmode:       {:o}\n",
+        attrs.mtime.unix_seconds(),
+        attrs.mtime.nanoseconds(),
+        attrs.atime.unix_seconds(),
+        attrs.atime.nanoseconds(),
+        attrs.mode
+    )
+}
+
+/// Accumulates `mtime:`/`atime:`/`mode:` lines while parsing a meta file or
+/// hash sidecar, alongside whatever other fields that format also carries.
+#[derive(Default)]
+struct ParsedAttrs {
+    mtime: Option<FileTime>,
+    atime: Option<FileTime>,
+    mode: Option<u32>,
+}
+
+impl ParsedAttrs {
+    fn consume_line(&mut self, line: &str) -> anyhow::Result<()> {
+        if let Some(rest) = line.strip_prefix("mtime:") {
+            self.mtime = Some(parse_timestamp(rest.trim())?);
+        } else if let Some(rest) = line.strip_prefix("atime:") {
+            self.atime = Some(parse_timestamp(rest.trim())?);
+        } else if let Some(rest) = line.strip_prefix("mode:") {
+            self.mode = Some(
+                u32::from_str_radix(rest.trim(), 8)
+                    .map_err(|err| anyhow::anyhow!("malformed mode: {}", err))?,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn finish(self, what: &str) -> anyhow::Result<FileAttrs> {
+        Ok(FileAttrs {
+            mtime: self
+                .mtime
+                .ok_or_else(|| anyhow::anyhow!("{} is missing an mtime: line", what))?,
+            atime: self
+                .atime
+                .ok_or_else(|| anyhow::anyhow!("{} is missing an atime: line", what))?,
+            mode: self
+                .mode
+                .ok_or_else(|| anyhow::anyhow!("{} is missing a mode: line", what))?,
+        })
+    }
+}
+
+/// Parses a `seconds.nanoseconds` timestamp, as written by [`format_attrs`].
+fn parse_timestamp(value: &str) -> anyhow::Result<FileTime> {
+    let (secs, nanos) = value
+        .split_once('.')
+        .ok_or_else(|| anyhow::anyhow!("malformed timestamp {:?}, expected seconds.nanoseconds", value))?;
+
+    Ok(FileTime::from_unix_time(
+        secs.parse()
+            .map_err(|err| anyhow::anyhow!("malformed timestamp seconds: {}", err))?,
+        nanos
+            .parse()
+            .map_err(|err| anyhow::anyhow!("malformed timestamp nanoseconds: {}", err))?,
+    ))
+}
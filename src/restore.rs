@@ -0,0 +1,191 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+use clap::Args as ClapArgs;
+
+use crate::meta::{hash_sidecar_extension, parse_hash_sidecar_content, parse_meta_content};
+use crate::NON_UNICODE_PATH;
+
+/// Rehydrates a previously generated meta tree back onto a real filesystem.
+/// Standard output lists properly processed entries separated by newlines.
+#[derive(ClapArgs, Debug)]
+pub struct RestoreArgs {
+    /// Path to a previously generated meta tree.
+    source: PathBuf,
+    /// Path to reconstruct the original layout onto.
+    destination: PathBuf,
+
+    /// Extension used for meta files.
+    #[arg(short, long, value_name = "EXTENSION", default_value = ".drivetan.txt")]
+    extension: String,
+
+    /// Magic expected at the start of a meta file.
+    #[arg(long, value_name = "MAGIC", default_value = "DRIVETAN")]
+    magic: String,
+}
+
+pub fn run(args: &RestoreArgs) -> anyhow::Result<()> {
+    check_args(args)?;
+
+    let walker = walkdir::WalkDir::new(&args.source).into_iter();
+
+    let mut success_entries: u128 = 0;
+    let mut error_entries: u128 = 0;
+    let mut skipped_entries: u128 = 0;
+
+    for entry in walker {
+        match entry {
+            Err(err) => {
+                eprintln!("could not read directory entry: {}", err);
+                error_entries += 1;
+            }
+            Ok(entry) => match handle_direntry(args, &entry) {
+                Err(err) => {
+                    eprintln!(
+                        "restoring directory entry {} failed: {}",
+                        entry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+                        err
+                    );
+                    error_entries += 1;
+                }
+                Ok(true) => {
+                    println!("{}", entry.path().to_str().unwrap_or(NON_UNICODE_PATH));
+                    success_entries += 1;
+                }
+                Ok(false) => skipped_entries += 1,
+            },
+        }
+    }
+
+    if success_entries == 0 {
+        anyhow::bail!(
+            "no entries successfuly restored; error count: {}",
+            error_entries
+        );
+    }
+
+    eprintln!(
+        "success count: {}, error count: {}, skipped count: {}",
+        success_entries, error_entries, skipped_entries
+    );
+    Ok(())
+}
+
+/// Restores a single meta tree entry. Returns `Ok(false)` for entries that
+/// are part of the meta tree's own bookkeeping (hash sidecars) rather than
+/// the original layout, so they're counted as skipped, not restored.
+fn handle_direntry(args: &RestoreArgs, direntry: &walkdir::DirEntry) -> anyhow::Result<bool> {
+    let diff = pathdiff::diff_paths(direntry.path(), &args.source);
+
+    match diff {
+        None => anyhow::bail!(
+            "could not diff paths: {}, {}",
+            direntry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+            &args.source.to_str().unwrap_or(NON_UNICODE_PATH)
+        ),
+        Some(diff) => {
+            let meta = direntry.metadata()?;
+
+            if meta.is_dir() {
+                std::fs::create_dir_all(args.destination.join(diff))?;
+            } else {
+                let file_name = direntry
+                    .file_name()
+                    .to_str()
+                    .ok_or_else(|| anyhow::anyhow!("non-unicode file name in meta tree"))?;
+
+                if file_name.ends_with(&hash_sidecar_extension(&args.extension)) {
+                    return Ok(false);
+                }
+
+                if let Some(original_name) = file_name.strip_suffix(&args.extension) {
+                    restore_placeholder(args, direntry, &diff, original_name)?;
+                } else {
+                    restore_verbatim(args, direntry, &diff)?;
+                }
+            }
+
+            Ok(true)
+        }
+    }
+}
+
+fn restore_placeholder(
+    args: &RestoreArgs,
+    direntry: &walkdir::DirEntry,
+    diff: &std::path::Path,
+    original_name: &str,
+) -> anyhow::Result<()> {
+    let content = std::fs::read(direntry.path())?;
+    let parsed = parse_meta_content(&content, &args.magic)?;
+
+    let mut dest = args.destination.join(diff);
+    dest.set_file_name(original_name);
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = std::fs::File::create(&dest)?;
+    file.set_len(parsed.size)?;
+
+    // The meta tree may have been copied, tar'd or rsync'd to another
+    // machine since `generate` ran, so the mtime/atime/mode recorded in
+    // the meta file itself are used rather than this file's own fs
+    // metadata, which reflects nothing about the original.
+    std::fs::set_permissions(&dest, std::fs::Permissions::from_mode(parsed.attrs.mode))?;
+    filetime::set_file_times(&dest, parsed.attrs.atime, parsed.attrs.mtime)?;
+
+    Ok(())
+}
+
+fn restore_verbatim(args: &RestoreArgs, direntry: &walkdir::DirEntry, diff: &std::path::Path) -> anyhow::Result<()> {
+    let dest = args.destination.join(diff);
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::copy(direntry.path(), &dest)?;
+
+    let mut sidecar_name = direntry.file_name().to_os_string();
+    sidecar_name.push(hash_sidecar_extension(&args.extension));
+    let sidecar_path = direntry.path().with_file_name(sidecar_name);
+    let parsed = parse_hash_sidecar_content(&std::fs::read(&sidecar_path)?)?;
+
+    // See the comment in `restore_placeholder`: attrs come from the
+    // sidecar, not this copy's own (possibly transport-mangled) fs metadata.
+    std::fs::set_permissions(&dest, std::fs::Permissions::from_mode(parsed.attrs.mode))?;
+    filetime::set_file_times(&dest, parsed.attrs.atime, parsed.attrs.mtime)?;
+
+    Ok(())
+}
+
+fn check_args(args: &RestoreArgs) -> anyhow::Result<()> {
+    if !args.source.exists() {
+        anyhow::bail!(
+            "source path {} does not exist or cannot be accessed",
+            args.source.to_str().unwrap_or(NON_UNICODE_PATH)
+        );
+    }
+
+    if !args.destination.exists() {
+        match std::fs::create_dir(&args.destination) {
+            Ok(it) => it,
+            Err(err) => anyhow::bail!("creating destination directory failed: {}", err),
+        };
+    } else if match std::fs::read_dir(&args.destination) {
+        Ok(it) => it,
+        Err(err) => anyhow::bail!("reading destination directory failed: {}", err),
+    }
+    .next()
+    .is_some()
+    {
+        anyhow::bail!(
+            "destination path {} is not empty",
+            args.destination.to_str().unwrap_or(NON_UNICODE_PATH)
+        );
+    }
+
+    Ok(())
+}
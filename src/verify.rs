@@ -0,0 +1,236 @@
+use std::path::PathBuf;
+
+use clap::Args as ClapArgs;
+
+use crate::hashing::hash_file;
+use crate::meta::{hash_sidecar_extension, parse_hash_sidecar_content, parse_meta_content};
+use crate::NON_UNICODE_PATH;
+
+/// Confirms that a source directory still matches a previously generated
+/// meta tree, reporting mismatched, missing and extra entries on stdout.
+#[derive(ClapArgs, Debug)]
+pub struct VerifyArgs {
+    /// Original source directory the meta tree was generated from.
+    source: PathBuf,
+    /// Previously generated meta tree to verify against.
+    meta: PathBuf,
+
+    /// Extension used for meta files.
+    #[arg(short, long, value_name = "EXTENSION", default_value = ".drivetan.txt")]
+    extension: String,
+
+    /// Magic expected at the start of a meta file.
+    #[arg(long, value_name = "MAGIC", default_value = "DRIVETAN")]
+    magic: String,
+}
+
+pub fn run(args: &VerifyArgs) -> anyhow::Result<()> {
+    check_args(args)?;
+
+    let mut mismatched: u128 = 0;
+    let mut missing: u128 = 0;
+    let mut extra: u128 = 0;
+    let mut error_entries: u128 = 0;
+    let mut ok_entries: u128 = 0;
+
+    for entry in walkdir::WalkDir::new(&args.source) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                eprintln!("could not read source directory entry: {}", err);
+                continue;
+            }
+        };
+
+        if entry.file_type().is_dir() {
+            continue;
+        }
+
+        match verify_source_entry(args, &entry) {
+            Ok(EntryStatus::Matched) => ok_entries += 1,
+            Ok(EntryStatus::Mismatched) => {
+                mismatched += 1;
+            }
+            Ok(EntryStatus::NotFound) => {
+                println!("missing: {}", entry.path().to_str().unwrap_or(NON_UNICODE_PATH));
+                missing += 1;
+            }
+            Err(err) => {
+                eprintln!(
+                    "checking {} against meta tree failed: {}",
+                    entry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+                    err
+                );
+                error_entries += 1;
+            }
+        }
+    }
+
+    for entry in walkdir::WalkDir::new(&args.meta) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                eprintln!("could not read meta directory entry: {}", err);
+                continue;
+            }
+        };
+
+        if entry.file_type().is_dir() {
+            continue;
+        }
+
+        if is_extra_entry(args, &entry)? {
+            println!("extra: {}", entry.path().to_str().unwrap_or(NON_UNICODE_PATH));
+            extra += 1;
+        }
+    }
+
+    eprintln!(
+        "ok count: {}, mismatched count: {}, missing count: {}, extra count: {}, error count: {}",
+        ok_entries, mismatched, missing, extra, error_entries
+    );
+
+    if mismatched > 0 || missing > 0 || extra > 0 || error_entries > 0 {
+        anyhow::bail!(
+            "verification failed: {} mismatched, {} missing, {} extra, {} errored",
+            mismatched,
+            missing,
+            extra,
+            error_entries
+        );
+    }
+
+    Ok(())
+}
+
+/// What checking a single source file against its meta tree counterpart
+/// found.
+enum EntryStatus {
+    /// Found, and the recorded size/hash agrees.
+    Matched,
+    /// Found, but the recorded size/hash disagrees (printed by the callee).
+    Mismatched,
+    /// No meta tree counterpart exists at all.
+    NotFound,
+}
+
+/// Checks a single source file against its meta tree counterpart. Failures
+/// to read the source file, or to read or parse a counterpart that does
+/// exist, are returned as `Err`, so they surface as errors rather than being
+/// conflated with `NotFound`.
+fn verify_source_entry(args: &VerifyArgs, source_entry: &walkdir::DirEntry) -> anyhow::Result<EntryStatus> {
+    let diff = pathdiff::diff_paths(source_entry.path(), &args.source).ok_or_else(|| {
+        anyhow::anyhow!(
+            "could not diff paths: {}, {}",
+            source_entry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+            args.source.to_str().unwrap_or(NON_UNICODE_PATH)
+        )
+    })?;
+
+    let meta_path = args.meta.join(&diff);
+    let mut placeholder_path = meta_path.clone();
+    let mut file_name = placeholder_path
+        .file_name()
+        .expect("not dir, should have a file name")
+        .to_os_string();
+    file_name.push(&args.extension);
+    placeholder_path.set_file_name(file_name);
+
+    let path = source_entry.path();
+    let current_len = source_entry.metadata()?.len();
+
+    if placeholder_path.exists() {
+        let parsed = parse_meta_content(&std::fs::read(&placeholder_path)?, &args.magic)?;
+
+        if parsed.size != current_len {
+            println!(
+                "mismatch: {} (recorded size {}, found {})",
+                path.to_str().unwrap_or(NON_UNICODE_PATH),
+                parsed.size,
+                current_len
+            );
+            return Ok(EntryStatus::Mismatched);
+        }
+
+        if let Some(recorded_hash) = parsed.hash {
+            let current_hash = hash_file(path, recorded_hash.algo)?;
+            if current_hash != recorded_hash {
+                println!("mismatch: {}", path.to_str().unwrap_or(NON_UNICODE_PATH));
+                return Ok(EntryStatus::Mismatched);
+            }
+        }
+
+        Ok(EntryStatus::Matched)
+    } else if meta_path.exists() {
+        let mut sidecar_name = meta_path
+            .file_name()
+            .expect("not dir, should have a file name")
+            .to_os_string();
+        sidecar_name.push(hash_sidecar_extension(&args.extension));
+        let sidecar_path = meta_path.with_file_name(sidecar_name);
+
+        if sidecar_path.exists() {
+            let recorded = parse_hash_sidecar_content(&std::fs::read(&sidecar_path)?)?;
+            let current_hash = hash_file(path, recorded.hash.algo)?;
+            if current_hash != recorded.hash {
+                println!("mismatch: {}", path.to_str().unwrap_or(NON_UNICODE_PATH));
+                return Ok(EntryStatus::Mismatched);
+            }
+        } else if meta_path.metadata()?.len() != current_len {
+            println!(
+                "mismatch: {} (no hash recorded, sizes differ)",
+                path.to_str().unwrap_or(NON_UNICODE_PATH)
+            );
+            return Ok(EntryStatus::Mismatched);
+        }
+
+        Ok(EntryStatus::Matched)
+    } else {
+        Ok(EntryStatus::NotFound)
+    }
+}
+
+/// Checks whether a meta tree entry's corresponding source file is gone.
+fn is_extra_entry(args: &VerifyArgs, meta_entry: &walkdir::DirEntry) -> anyhow::Result<bool> {
+    let diff = pathdiff::diff_paths(meta_entry.path(), &args.meta).ok_or_else(|| {
+        anyhow::anyhow!(
+            "could not diff paths: {}, {}",
+            meta_entry.path().to_str().unwrap_or(NON_UNICODE_PATH),
+            args.meta.to_str().unwrap_or(NON_UNICODE_PATH)
+        )
+    })?;
+
+    let file_name = meta_entry
+        .file_name()
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("non-unicode file name in meta tree"))?;
+
+    if file_name.ends_with(&hash_sidecar_extension(&args.extension)) {
+        return Ok(false);
+    }
+
+    let mut source_path = args.source.join(&diff);
+    if let Some(original_name) = file_name.strip_suffix(&args.extension) {
+        source_path.set_file_name(original_name);
+    }
+
+    Ok(!source_path.exists())
+}
+
+fn check_args(args: &VerifyArgs) -> anyhow::Result<()> {
+    if !args.source.exists() {
+        anyhow::bail!(
+            "source path {} does not exist or cannot be accessed",
+            args.source.to_str().unwrap_or(NON_UNICODE_PATH)
+        );
+    }
+
+    if !args.meta.exists() {
+        anyhow::bail!(
+            "meta path {} does not exist or cannot be accessed",
+            args.meta.to_str().unwrap_or(NON_UNICODE_PATH)
+        );
+    }
+
+    Ok(())
+}